@@ -1,19 +1,19 @@
-use ipnet::Ipv4Net;
+use ipnet::IpNet;
 use log::{debug, trace};
 use std::collections::HashSet;
-use std::net::SocketAddrV4;
+use std::net::SocketAddr;
 
 pub struct AddressFilter {
-    transmit_addresses_set: HashSet<SocketAddrV4>,
-    block_nets: Vec<Ipv4Net>,
-    allow_nets: Vec<Ipv4Net>,
+    transmit_addresses_set: HashSet<SocketAddr>,
+    block_nets: Vec<IpNet>,
+    allow_nets: Vec<IpNet>,
 }
 
 impl AddressFilter {
     pub fn new(
-        transmit_addresses_set: HashSet<SocketAddrV4>,
-        block_nets: Vec<Ipv4Net>,
-        allow_nets: Vec<Ipv4Net>,
+        transmit_addresses_set: HashSet<SocketAddr>,
+        block_nets: Vec<IpNet>,
+        allow_nets: Vec<IpNet>,
     ) -> Self {
         // TODO: only log if non-zero?
         debug!("Blocking packets from {} subnets", block_nets.len());
@@ -25,18 +25,18 @@ impl AddressFilter {
         }
     }
 
-    pub fn should_transmit(&self, socket_addr: &SocketAddrV4) -> bool {
+    pub fn should_transmit(&self, socket_addr: &SocketAddr) -> bool {
         let storm_check = self.transmit_addresses_set.contains(socket_addr);
 
         let in_block_net = self
             .block_nets
             .iter()
-            .any(|net| net.contains(socket_addr.ip()));
+            .any(|net| net.contains(&socket_addr.ip()));
 
         let in_allow_net = self
             .allow_nets
             .iter()
-            .any(|net| net.contains(socket_addr.ip()));
+            .any(|net| net.contains(&socket_addr.ip()));
 
         // TODO: Check semantics of this.
         let res = !storm_check && (!in_block_net || in_allow_net);
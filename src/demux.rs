@@ -0,0 +1,129 @@
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+pub type Packet = (Vec<u8>, SocketAddr);
+
+const PER_SOURCE_QUEUE_SIZE: usize = 64;
+
+pub struct Demultiplexer {
+    senders: Mutex<HashMap<SocketAddr, (mpsc::Sender<Packet>, Instant)>>,
+    catch_all: mpsc::Sender<Packet>,
+}
+
+impl Demultiplexer {
+    pub fn new() -> (Self, mpsc::Receiver<Packet>) {
+        let (catch_all, catch_all_rx) = mpsc::channel(PER_SOURCE_QUEUE_SIZE);
+        (
+            Self {
+                senders: Mutex::new(HashMap::new()),
+                catch_all,
+            },
+            catch_all_rx,
+        )
+    }
+
+    pub fn register_source_if_new(&self, source_addr: SocketAddr) -> Option<mpsc::Receiver<Packet>> {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.contains_key(&source_addr) {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel(PER_SOURCE_QUEUE_SIZE);
+        senders.insert(source_addr, (tx, Instant::now()));
+        Some(rx)
+    }
+
+    pub fn dispatch(&self, buf: Vec<u8>, source_addr: SocketAddr) {
+        let sender = {
+            let mut senders = self.senders.lock().unwrap();
+            senders.get_mut(&source_addr).map(|(tx, last_seen)| {
+                *last_seen = Instant::now();
+                tx.clone()
+            })
+        };
+
+        let result = match sender {
+            Some(tx) => tx.try_send((buf, source_addr)),
+            None => self.catch_all.try_send((buf, source_addr)),
+        };
+
+        if let Err(e) = result {
+            warn!("Dropped packet from {:?}: {:?}", source_addr, e);
+        }
+    }
+
+    // Dropping a sender here lets its run_source_queue consumer exit once recv() sees the
+    // channel has closed.
+    pub fn housekeep(&self, ttl: Duration) {
+        let now = Instant::now();
+        let mut senders = self.senders.lock().unwrap();
+        let before = senders.len();
+        senders.retain(|_, (_, last_seen)| now.duration_since(*last_seen) < ttl);
+        let removed = before - senders.len();
+        drop(senders);
+
+        if removed > 0 {
+            debug!("Evicted {} idle demultiplexer queues", removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread::sleep;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)
+    }
+
+    #[test]
+    fn register_source_if_new_only_opens_a_queue_once() {
+        let (demux, _catch_all_rx) = Demultiplexer::new();
+        assert!(demux.register_source_if_new(addr(1)).is_some());
+        assert!(demux.register_source_if_new(addr(1)).is_none());
+    }
+
+    #[test]
+    fn dispatch_routes_to_the_registered_source_queue() {
+        let (demux, mut catch_all_rx) = Demultiplexer::new();
+        let mut source_rx = demux.register_source_if_new(addr(1)).unwrap();
+
+        demux.dispatch(vec![1, 2, 3], addr(1));
+
+        let (buf, source_addr) = source_rx.try_recv().unwrap();
+        assert_eq!(buf, vec![1, 2, 3]);
+        assert_eq!(source_addr, addr(1));
+        assert!(catch_all_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn dispatch_falls_back_to_catch_all_for_unregistered_sources() {
+        let (demux, mut catch_all_rx) = Demultiplexer::new();
+
+        demux.dispatch(vec![9], addr(2));
+
+        let (buf, source_addr) = catch_all_rx.try_recv().unwrap();
+        assert_eq!(buf, vec![9]);
+        assert_eq!(source_addr, addr(2));
+    }
+
+    #[test]
+    fn housekeep_evicts_idle_queues_only() {
+        let (demux, _catch_all_rx) = Demultiplexer::new();
+        let _rx1 = demux.register_source_if_new(addr(1)).unwrap();
+        sleep(Duration::from_millis(20));
+        let _rx2 = demux.register_source_if_new(addr(2)).unwrap();
+
+        demux.housekeep(Duration::from_millis(10));
+
+        assert!(demux.register_source_if_new(addr(1)).is_some());
+        assert!(demux.register_source_if_new(addr(2)).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+use log::{debug, trace};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::protocol::InnerAddr;
+
+pub struct Table {
+    entries: HashMap<InnerAddr, (SocketAddr, Instant)>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn learn(&mut self, inner_addr: InnerAddr, source_addr: SocketAddr) {
+        trace!("Learned {} is reachable via {:?}", inner_addr, source_addr);
+        self.entries
+            .insert(inner_addr, (source_addr, Instant::now()));
+    }
+
+    pub fn lookup(&self, inner_addr: &InnerAddr) -> Option<SocketAddr> {
+        self.entries.get(inner_addr).map(|(addr, _)| *addr)
+    }
+
+    pub fn housekeep(&mut self, ttl: Duration) {
+        let before = self.entries.len();
+        let now = Instant::now();
+        self.entries
+            .retain(|_, (_, last_seen)| now.duration_since(*last_seen) < ttl);
+
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            debug!("Evicted {} stale forwarding entries", removed);
+        }
+    }
+
+    pub fn remove_all(&mut self, source_addr: &SocketAddr) {
+        self.entries.retain(|_, (addr, _)| addr != source_addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread::sleep;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)
+    }
+
+    fn mac(last: u8) -> InnerAddr {
+        InnerAddr::Mac([0, 0, 0, 0, 0, last])
+    }
+
+    #[test]
+    fn lookup_returns_none_before_learning() {
+        let table = Table::new();
+        assert_eq!(table.lookup(&mac(1)), None);
+    }
+
+    #[test]
+    fn learn_then_lookup_returns_source() {
+        let mut table = Table::new();
+        table.learn(mac(1), addr(1000));
+        assert_eq!(table.lookup(&mac(1)), Some(addr(1000)));
+    }
+
+    #[test]
+    fn learn_overwrites_previous_source() {
+        let mut table = Table::new();
+        table.learn(mac(1), addr(1000));
+        table.learn(mac(1), addr(2000));
+        assert_eq!(table.lookup(&mac(1)), Some(addr(2000)));
+    }
+
+    #[test]
+    fn housekeep_evicts_stale_entries_only() {
+        let mut table = Table::new();
+        table.learn(mac(1), addr(1000));
+        sleep(Duration::from_millis(20));
+        table.learn(mac(2), addr(2000));
+
+        table.housekeep(Duration::from_millis(10));
+
+        assert_eq!(table.lookup(&mac(1)), None);
+        assert_eq!(table.lookup(&mac(2)), Some(addr(2000)));
+    }
+
+    #[test]
+    fn remove_all_drops_every_entry_for_source() {
+        let mut table = Table::new();
+        table.learn(mac(1), addr(1000));
+        table.learn(mac(2), addr(1000));
+        table.learn(mac(3), addr(2000));
+
+        table.remove_all(&addr(1000));
+
+        assert_eq!(table.lookup(&mac(1)), None);
+        assert_eq!(table.lookup(&mac(2)), None);
+        assert_eq!(table.lookup(&mac(3)), Some(addr(2000)));
+    }
+}
@@ -1,44 +1,201 @@
+mod address_filter;
+mod demux;
+mod forwarding_table;
+mod peer_list;
+mod protocol;
+mod rendezvous;
+
 use std::collections::{HashMap, HashSet};
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use std::process;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use ipnet::Ipv4Net;
+use ipnet::IpNet;
 use log::{debug, error, info, trace, warn};
 
 use clap::Parser;
 
 use network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig};
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::io;
 use tokio::net::UdpSocket;
-use tokio::sync::broadcast::{self, Sender};
+
+use address_filter::AddressFilter;
+use demux::Demultiplexer;
+use forwarding_table::Table as ForwardingTable;
+use peer_list::{ControlMessage, PeerList};
+use protocol::ProtocolKind;
+use rendezvous::RendezvousTable;
 
 const BUFFER_SIZE: usize = 4096 + 20 + 8;
-const TRANSMIT_PORT: u16 = 58371;
-const CHANNEL_SIZE: usize = 2 << 7;
+const HOUSEKEEP_INTERVAL: Duration = Duration::from_secs(5);
+const PEER_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Which address family(ies) an interface should contribute sockets for.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+    Both,
+}
+
+/// A single `--transmit-ports` entry: either a bare port or an inclusive range.
+#[derive(Debug, Clone)]
+enum PortSpec {
+    Port(u16),
+    Range(u16, u16),
+}
+
+impl PortSpec {
+    fn expand(&self) -> Vec<u16> {
+        match self {
+            PortSpec::Port(port) => vec![*port],
+            PortSpec::Range(start, end) => (*start..=*end).collect(),
+        }
+    }
+}
+
+fn parse_port_spec(s: &str) -> Result<PortSpec, String> {
+    match s.split_once('-') {
+        Some((start, end)) => {
+            let start: u16 = start
+                .parse()
+                .map_err(|_| format!("invalid port range start: {start:?}"))?;
+            let end: u16 = end
+                .parse()
+                .map_err(|_| format!("invalid port range end: {end:?}"))?;
+            if start > end {
+                return Err(format!("invalid port range: {start}-{end} (start > end)"));
+            }
+            Ok(PortSpec::Range(start, end))
+        }
+        None => s
+            .parse()
+            .map(PortSpec::Port)
+            .map_err(|_| format!("invalid port: {s:?}")),
+    }
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
+enum Cli {
+    /// Run the relay (the historical mode: receive, filter, learn, and forward).
+    Relay(RelayArgs),
+    /// Register with a `--rendezvous` server, print the learned peer endpoints, and begin
+    /// keepalive pinging them to hold the punched NAT mapping open.
+    RendezvousClient(RendezvousClientArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct RelayArgs {
     #[arg(short, long, required = true)]
     port: u16,
 
+    /// Port for the Hello/HelloAck/rendezvous control protocol, bound separately from `port` so
+    /// relayed data payloads can never be mistaken for (or forge) a control message. Defaults to
+    /// `port + 1`.
+    #[arg(long)]
+    control_port: Option<u16>,
+
     #[arg(short, long, value_delimiter = ',', required = true)]
     receive_interfaces: Vec<String>,
 
     #[arg(short, long, value_delimiter = ',', required = true)]
     transmit_interfaces: Vec<String>,
 
+    /// Ports to fan transmitted packets out to, as a comma-delimited list of ports and/or
+    /// inclusive ranges (e.g. `58372,58373` or `58372-58380`).
+    #[arg(long, value_delimiter = ',', required = true, value_parser = parse_port_spec)]
+    transmit_ports: Vec<PortSpec>,
+
+    /// How to parse the encapsulated payload to recover inner source/destination addresses for
+    /// the learning forwarding table. `raw` never resolves any, so every packet floods.
+    #[arg(long, value_enum, default_value_t = ProtocolKind::Raw)]
+    protocol: ProtocolKind,
+
+    #[arg(long, value_delimiter = ',')]
+    block_nets: Vec<IpNet>,
+
     #[arg(long, value_delimiter = ',')]
-    block_nets: Vec<Ipv4Net>,
+    allow_nets: Vec<IpNet>,
+
+    /// Which address family(ies) every listed interface contributes sockets for. An interface
+    /// with no address of the chosen family contributes none.
+    #[arg(long, value_enum, default_value_t = Family::Both)]
+    prefer_family: Family,
+
+    /// How long (in seconds) a learned forwarding entry stays valid without being refreshed.
+    #[arg(long, default_value_t = 30)]
+    learning_timeout: u64,
 
+    /// Flood to every transmit address when the destination hasn't been learned yet. Disabling
+    /// this drops packets to unknown destinations instead.
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    flood_unknown: bool,
+
+    /// Statically-configured peer control endpoints (`ip:control_port`) to keep probing with
+    /// `Hello`s until they answer.
     #[arg(long, value_delimiter = ',')]
-    allow_nets: Vec<Ipv4Net>,
+    reconnect_peers: Vec<SocketAddr>,
+
+    /// How long (in seconds) a peer can go without a heartbeat before it's dropped from the
+    /// live transmit set (also used to expire rendezvous registrations).
+    #[arg(long, default_value_t = 60)]
+    peer_timeout: u64,
+
+    /// How long (in seconds) a per-source demultiplexer queue can go without a packet before
+    /// it's torn down, bounding memory/FD usage from sources that come and go.
+    #[arg(long, default_value_t = 120)]
+    demux_idle_timeout: u64,
+
+    /// Act as a rendezvous server: record peers' observed external endpoints and hand each peer
+    /// the others' endpoints so they can hole-punch a direct path.
+    #[arg(long, default_value_t = false)]
+    rendezvous: bool,
+
+    #[command(flatten)]
+    verbose: clap_verbosity_flag::Verbosity,
+}
+
+#[derive(clap::Args, Debug)]
+struct RendezvousClientArgs {
+    /// The rendezvous server's control endpoint (`ip:control_port`) to register with.
+    #[arg(long)]
+    rendezvous_server: SocketAddr,
+
+    /// Local address to bind. Reused for both registration and the punched datagrams, so the
+    /// socket is created with SO_REUSEADDR/SO_REUSEPORT.
+    #[arg(long)]
+    bind: SocketAddr,
+
+    /// How often (in seconds) to re-ping learned peers to keep the punched mapping alive.
+    #[arg(long, default_value_t = 10)]
+    keepalive_interval: u64,
 
     #[command(flatten)]
     verbose: clap_verbosity_flag::Verbosity,
 }
 
+/// Bind a UDP socket with SO_REUSEADDR/SO_REUSEPORT set, so the same local port can later be
+/// reused to source punched datagrams straight to a peer learned via rendezvous.
+fn bind_reuseport(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let domain = if addr.is_ipv4() {
+        Domain::IPV4
+    } else {
+        Domain::IPV6
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+
+    UdpSocket::from_std(socket.into())
+}
+
 fn get_interface_map() -> HashMap<String, NetworkInterface> {
     let interface_map: HashMap<String, NetworkInterface> = NetworkInterface::show()
         .unwrap()
@@ -51,21 +208,34 @@ fn get_interface_map() -> HashMap<String, NetworkInterface> {
     interface_map
 }
 
+// fe80::/10.
+fn is_unicast_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
 fn get_socket_addresses(
     interfaces: &[String],
     interface_map: &HashMap<String, NetworkInterface>,
     port: u16,
-) -> Option<Vec<SocketAddrV4>> {
-    let addrs: Vec<SocketAddrV4> = interfaces
+    prefer_family: Family,
+) -> Option<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = interfaces
         .iter()
         .filter_map(|interface_name| {
             interface_map
                 .get(interface_name)
-                .map(|NetworkInterface { addr, .. }| addr)
+                .map(|NetworkInterface { addr, index, .. }| (*index, addr))
         })
-        .flat_map(|addrs| {
-            addrs.iter().filter_map(|addr| match addr {
-                Addr::V4(addr) => Some(SocketAddrV4::new(addr.ip, port)),
+        .flat_map(|(index, addrs)| {
+            addrs.iter().filter_map(move |addr| match addr {
+                Addr::V4(addr) if prefer_family != Family::V6 => {
+                    Some(SocketAddr::V4(SocketAddrV4::new(addr.ip, port)))
+                }
+                Addr::V6(addr) if prefer_family != Family::V4 => {
+                    // Link-local addresses are only routable with their interface's scope id.
+                    let scope_id = if is_unicast_link_local(&addr.ip) { index } else { 0 };
+                    Some(SocketAddr::V6(SocketAddrV6::new(addr.ip, port, 0, scope_id)))
+                }
                 _ => None,
             })
         })
@@ -77,109 +247,187 @@ fn get_socket_addresses(
     }
 }
 
-struct AddressFilter {
-    transmit_addresses_set: HashSet<SocketAddrV4>,
-    block_nets: Vec<Ipv4Net>,
-    allow_nets: Vec<Ipv4Net>,
-}
-
-impl AddressFilter {
-    fn new(
-        transmit_addresses_set: HashSet<SocketAddrV4>,
-        block_nets: Vec<Ipv4Net>,
-        allow_nets: Vec<Ipv4Net>,
-    ) -> Self {
-        // TODO: only log if non-zero?
-        debug!("Blocking packets from {} subnets", block_nets.len());
-        debug!("Allowing packets from {} subnets", allow_nets.len());
-        Self {
-            transmit_addresses_set,
-            block_nets,
-            allow_nets,
-        }
-    }
-
-    fn should_transmit(&self, socket_addr: &SocketAddrV4) -> bool {
-        let storm_check = self.transmit_addresses_set.contains(socket_addr);
-
-        let in_block_net = self
-            .block_nets
-            .iter()
-            .any(|net| net.contains(socket_addr.ip()));
-
-        let in_allow_net = self
-            .allow_nets
-            .iter()
-            .any(|net| net.contains(socket_addr.ip()));
-
-        // TODO: Check semantics of this.
-        let res = !storm_check && (!in_block_net || in_allow_net);
+// Control messages are demultiplexed on their own port+socket (see `control_handler`), so a
+// relayed data payload can never be mistaken for (or forged as) a control message here.
+async fn receive_handler(
+    demux: Arc<Demultiplexer>,
+    receive_sock: Arc<UdpSocket>,
+    address_filter: Arc<AddressFilter>,
+    forwarding_table: Arc<Mutex<ForwardingTable>>,
+    protocol: Arc<dyn protocol::Protocol>,
+) {
+    let mut buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+    let (len, source_addr) = receive_sock.recv_from(&mut buf).await.unwrap();
+    debug!("Read {} bytes from {:?}", len, source_addr);
 
-        if !res {
-            trace!("Not transmitting packet from {:?}", socket_addr);
+    if address_filter.should_transmit(&source_addr) {
+        if let Ok((inner_src, _inner_dst)) = protocol.parse(&buf[..len]) {
+            forwarding_table.lock().unwrap().learn(inner_src, source_addr);
         }
 
-        res
+        demux.dispatch(buf[..len].to_vec(), source_addr);
     }
 }
 
-async fn receive_handler(
-    tx: Sender<(Vec<u8>, SocketAddr)>,
-    receive_sock: Arc<UdpSocket>,
+/// Handles Hello/HelloAck/rendezvous control traffic on the dedicated control socket. Peers are
+/// registered under `(source_addr.ip(), data_port)`, since that's the address data should
+/// actually be flooded to, not the control socket's own address.
+async fn control_handler(
+    control_sock: Arc<UdpSocket>,
     address_filter: Arc<AddressFilter>,
+    peer_list: Arc<Mutex<PeerList>>,
+    rendezvous_table: Option<Arc<Mutex<RendezvousTable>>>,
+    data_port: u16,
 ) {
     let mut buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
-    let (len, source_addr) = receive_sock.recv_from(&mut buf).await.unwrap();
-    debug!("Read {} bytes from {:?}", len, source_addr);
+    let (len, source_addr) = control_sock.recv_from(&mut buf).await.unwrap();
+
+    if !address_filter.should_transmit(&source_addr) {
+        trace!("Ignoring control message from blocked source {:?}", source_addr);
+        return;
+    }
 
-    if let SocketAddr::V4(inner) = source_addr {
-        if address_filter.should_transmit(&inner) {
-            match tx.send((buf[..len].to_vec(), source_addr)) {
-                Ok(_) => trace!("Added packet to channel from {:?}", source_addr),
-                Err(e) => {
+    let Some(msg) = ControlMessage::decode(&buf[..len]) else {
+        trace!("Ignoring non-control datagram on control port from {:?}", source_addr);
+        return;
+    };
+
+    let data_addr = SocketAddr::new(source_addr.ip(), data_port);
+
+    match msg {
+        ControlMessage::Hello => {
+            peer_list.lock().unwrap().register(data_addr);
+            let ack = ControlMessage::HelloAck.encode();
+            if let Err(e) = control_sock.send_to(&ack, source_addr).await {
+                warn!("Failed to ack hello from {:?}: {:?}", source_addr, e);
+            }
+        }
+        ControlMessage::HelloAck => {
+            peer_list.lock().unwrap().register(data_addr);
+        }
+        ControlMessage::RendezvousHello => match &rendezvous_table {
+            Some(rendezvous_table) => {
+                let peers = {
+                    let mut rendezvous_table = rendezvous_table.lock().unwrap();
+                    rendezvous_table.register(source_addr);
+                    rendezvous_table.peers_excluding(&source_addr)
+                };
+
+                let reply = ControlMessage::RendezvousPeers(peers).encode();
+                if let Err(e) = control_sock.send_to(&reply, source_addr).await {
                     warn!(
-                        "Error adding packet to channel from {:?} {:?}",
+                        "Failed to reply to rendezvous hello from {:?}: {:?}",
                         source_addr, e
                     );
                 }
             }
+            None => trace!(
+                "Ignoring rendezvous hello from {:?}, rendezvous mode is disabled",
+                source_addr
+            ),
+        },
+        ControlMessage::RendezvousPeers(_) => {
+            trace!("Ignoring unsolicited rendezvous peers from {:?}", source_addr);
         }
-    } else {
-        trace!("Ignoring non-IPv4 packet from {}", source_addr);
     }
 }
 
-#[tokio::main]
-async fn main() -> io::Result<()> {
-    let args = Args::parse();
+/// Shared state needed to decide where a dequeued packet goes and actually send it.
+struct ForwardContext {
+    forwarding_table: Arc<Mutex<ForwardingTable>>,
+    peer_list: Arc<Mutex<PeerList>>,
+    transmit_sock_v4: Arc<UdpSocket>,
+    transmit_sock_v6: Arc<UdpSocket>,
+    flood_unknown: bool,
+    protocol: Arc<dyn protocol::Protocol>,
+}
+
+async fn forward_packet(ctx: &ForwardContext, buf: Vec<u8>) {
+    let unicast_target = ctx.protocol.parse(&buf).ok().and_then(|(_inner_src, inner_dst)| {
+        ctx.forwarding_table.lock().unwrap().lookup(&inner_dst)
+    });
+
+    let targets: Vec<SocketAddr> = match unicast_target {
+        Some(target) => vec![target],
+        None if ctx.flood_unknown => ctx.peer_list.lock().unwrap().snapshot(),
+        None => {
+            trace!("Dropping packet to unlearned destination");
+            Vec::new()
+        }
+    };
+
+    for transmit_address in targets {
+        let transmit_sock = match transmit_address {
+            SocketAddr::V4(_) => &ctx.transmit_sock_v4,
+            SocketAddr::V6(_) => &ctx.transmit_sock_v6,
+        };
+
+        match transmit_sock.send_to(&buf, transmit_address).await {
+            Ok(n) => debug!("Sent {n} bytes to {transmit_address}"),
+            Err(e) => {
+                error!("Send failed to {:?}, {:?}", transmit_address, e);
+                ctx.forwarding_table.lock().unwrap().remove_all(&transmit_address);
+            }
+        }
+    }
+}
+
+/// Drains a single source's dedicated queue, forwarding each packet in turn.
+async fn run_source_queue(mut rx: tokio::sync::mpsc::Receiver<demux::Packet>, ctx: Arc<ForwardContext>) {
+    while let Some((buf, _source_addr)) = rx.recv().await {
+        forward_packet(&ctx, buf).await;
+    }
+}
 
-    env_logger::Builder::new()
-        .filter_level(args.verbose.log_level_filter())
-        .init();
+/// Drains the catch-all queue of first-sight packets, spinning up a dedicated per-source queue
+/// (and its own `run_source_queue` consumer) the first time each source address is seen.
+async fn run_catch_all(
+    mut rx: tokio::sync::mpsc::Receiver<demux::Packet>,
+    demux: Arc<Demultiplexer>,
+    ctx: Arc<ForwardContext>,
+) {
+    while let Some((buf, source_addr)) = rx.recv().await {
+        if let Some(source_rx) = demux.register_source_if_new(source_addr) {
+            trace!("First packet seen from {:?}, opening a dedicated queue", source_addr);
+            tokio::spawn(run_source_queue(source_rx, ctx.clone()));
+        }
+
+        forward_packet(&ctx, buf).await;
+    }
+}
 
+async fn run_relay(args: RelayArgs) -> io::Result<()> {
     info!("Starting up");
 
     let interface_map: HashMap<String, NetworkInterface> = get_interface_map();
 
-    let receive_addresses =
-        match get_socket_addresses(&args.receive_interfaces, &interface_map, args.port) {
-            Some(addrs) => addrs,
-            None => {
-                error!(
-                    "No interfaces to receive from. Tried {:?}",
-                    &args.receive_interfaces
-                );
-                process::exit(1);
-            }
-        };
+    let receive_addresses = match get_socket_addresses(
+        &args.receive_interfaces,
+        &interface_map,
+        args.port,
+        args.prefer_family,
+    ) {
+        Some(addrs) => addrs,
+        None => {
+            error!(
+                "No interfaces to receive from. Tried {:?}",
+                &args.receive_interfaces
+            );
+            process::exit(1);
+        }
+    };
 
-    // TODO: Pull this from args
-    let transmit_ports: Vec<u16> = (1..3).map(|i| args.port + i).collect();
+    let transmit_ports: Vec<u16> = args.transmit_ports.iter().flat_map(PortSpec::expand).collect();
 
-    let transmit_addresses: Vec<SocketAddrV4> = transmit_ports
+    let transmit_addresses: Vec<SocketAddr> = transmit_ports
         .iter()
         .flat_map(|transmit_port| {
-            match get_socket_addresses(&args.transmit_interfaces, &interface_map, *transmit_port) {
+            match get_socket_addresses(
+                &args.transmit_interfaces,
+                &interface_map,
+                *transmit_port,
+                args.prefer_family,
+            ) {
                 Some(addrs) => addrs,
                 None => {
                     error!(
@@ -195,80 +443,359 @@ async fn main() -> io::Result<()> {
     debug!("Receiving from {} interfaces", receive_addresses.len());
     debug!("Transmitting to {} interfaces", transmit_addresses.len());
 
-    let transmit_addresses_set: HashSet<SocketAddrV4> =
+    let transmit_addresses_set: HashSet<SocketAddr> =
         HashSet::from_iter(transmit_addresses.iter().cloned());
 
     let address_filter =
         AddressFilter::new(transmit_addresses_set, args.block_nets, args.allow_nets);
     let address_filter = Arc::new(address_filter);
 
-    // TODO: consider channel size here
-    let (tx, _rx) = broadcast::channel::<(Vec<u8>, SocketAddr)>(CHANNEL_SIZE);
-    trace!("Created broadcast channel");
+    let protocol: Arc<dyn protocol::Protocol> = Arc::from(args.protocol.build());
+
+    let forwarding_table = Arc::new(Mutex::new(ForwardingTable::new()));
+    let learning_timeout = Duration::from_secs(args.learning_timeout);
+
+    {
+        let forwarding_table = forwarding_table.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HOUSEKEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                forwarding_table.lock().unwrap().housekeep(learning_timeout);
+            }
+        });
+    }
+
+    // Seed the live peer list with the statically-configured transmit addresses so the relay
+    // behaves as before until peers start registering (or de-registering) themselves at runtime.
+    let peer_list = {
+        let mut peer_list = PeerList::new(args.reconnect_peers);
+        for transmit_address in &transmit_addresses {
+            peer_list.register_static(*transmit_address);
+        }
+        Arc::new(Mutex::new(peer_list))
+    };
+    let peer_timeout = Duration::from_secs(args.peer_timeout);
+
+    {
+        let peer_list = peer_list.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HOUSEKEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                peer_list.lock().unwrap().prune(peer_timeout);
+            }
+        });
+    }
+
+    let rendezvous_table = if args.rendezvous {
+        let rendezvous_table = Arc::new(Mutex::new(RendezvousTable::new()));
+
+        {
+            let rendezvous_table = rendezvous_table.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(HOUSEKEEP_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    rendezvous_table.lock().unwrap().prune(peer_timeout);
+                }
+            });
+        }
+
+        Some(rendezvous_table)
+    } else {
+        None
+    };
+
+    // Datagrams are fanned out per-source instead of through one global channel, so a storm
+    // from one peer only fills (and drops from) that peer's own queue.
+    let (demux, catch_all_rx) = Demultiplexer::new();
+    let demux = Arc::new(demux);
+    let demux_idle_timeout = Duration::from_secs(args.demux_idle_timeout);
+
+    {
+        let demux = demux.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HOUSEKEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                demux.housekeep(demux_idle_timeout);
+            }
+        });
+    }
 
     // Set up incoming packet receivers. We bind a `UdpSocket` per-address in the
-    // `receive_addresses` collection. Note that the `tx` within the loop refers to the
-    // input-side of the `broadcast::channel` created above.
+    // `receive_addresses` collection. Note that `demux` within the loop is shared by every
+    // receive task, and routes each datagram to its source's own queue.
     for receive_address in receive_addresses {
-        let receive_sock = UdpSocket::bind(receive_address)
-            .await
-            .expect("Error creating socket");
+        let receive_sock =
+            bind_reuseport(receive_address).expect("Error creating socket");
         info!("Listening on {:?}", receive_address);
 
         let receive_sock = Arc::new(receive_sock);
-        let tx = tx.clone();
+        let demux = demux.clone();
         let address_filter = address_filter.clone();
+        let forwarding_table = forwarding_table.clone();
+        let protocol = protocol.clone();
 
         tokio::spawn(async move {
-            let tx = tx.clone();
             loop {
-                receive_handler(tx.clone(), receive_sock.clone(), address_filter.clone()).await
+                receive_handler(
+                    demux.clone(),
+                    receive_sock.clone(),
+                    address_filter.clone(),
+                    forwarding_table.clone(),
+                    protocol.clone(),
+                )
+                .await
             }
         });
     }
 
-    // Set up outing packet transmitters. Here, we bind a single `UdpSocket` on
-    // localhost:TRANSMIT_PORT, then use the `send_to` method to sent to the apprpriate
-    // `transmit_address`. Note that the `rx` within the loop refers to the output-side
-    // of the `broadcast::channel` created above.
+    // Control traffic (Hello/HelloAck/rendezvous) is bound on its own port, entirely separate
+    // from the data-plane sockets above, so relayed payloads can never be mistaken for it.
+    let control_port = args.control_port.unwrap_or_else(|| args.port.saturating_add(1));
+    let control_addresses = match get_socket_addresses(
+        &args.receive_interfaces,
+        &interface_map,
+        control_port,
+        args.prefer_family,
+    ) {
+        Some(addrs) => addrs,
+        None => {
+            error!(
+                "No interfaces to receive control traffic on. Tried {:?}",
+                &args.receive_interfaces
+            );
+            process::exit(1);
+        }
+    };
+
+    let mut control_socks: Vec<Arc<UdpSocket>> = Vec::new();
+    for control_address in control_addresses {
+        let control_sock =
+            bind_reuseport(control_address).expect("Error creating control socket");
+        info!("Listening for control traffic on {:?}", control_address);
+
+        let control_sock = Arc::new(control_sock);
+        control_socks.push(control_sock.clone());
+        let address_filter = address_filter.clone();
+        let peer_list = peer_list.clone();
+        let rendezvous_table = rendezvous_table.clone();
+        let data_port = args.port;
+
+        tokio::spawn(async move {
+            loop {
+                control_handler(
+                    control_sock.clone(),
+                    address_filter.clone(),
+                    peer_list.clone(),
+                    rendezvous_table.clone(),
+                    data_port,
+                )
+                .await
+            }
+        });
+    }
+
+    // Set up outgoing packet transmitters. Bound to the wildcard address (not loopback) so the
+    // kernel can actually route sends to real remote peers, not just other local interfaces;
+    // `forward_packet` picks whichever one matches a given destination's family.
     //
     // TODO: possible improvement - collection of open sockets?
-    let transmit_sock_addr = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), TRANSMIT_PORT);
+    let transmit_sock_addr_v4 = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
+    let transmit_sock_addr_v6 = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0);
+
+    let transmit_sock_v4 = bind_reuseport(SocketAddr::V4(transmit_sock_addr_v4))
+        .expect("Could not bind IPv4 transmit socket");
+    info!("Transmitting IPv4 from {:?}", transmit_sock_v4.local_addr().unwrap());
 
-    let transmit_sock = UdpSocket::bind(transmit_sock_addr)
-        .await
-        .expect("Could not bind transmit socket");
-    info!("Transmitting on {:?}", transmit_sock_addr);
+    let transmit_sock_v6 = bind_reuseport(SocketAddr::V6(transmit_sock_addr_v6))
+        .expect("Could not bind IPv6 transmit socket");
+    info!("Transmitting IPv6 from {:?}", transmit_sock_v6.local_addr().unwrap());
 
-    let transmit_sock = Arc::new(transmit_sock);
-    let transmit_addresses = Arc::new(transmit_addresses);
+    let transmit_sock_v4 = Arc::new(transmit_sock_v4);
+    let transmit_sock_v6 = Arc::new(transmit_sock_v6);
+    let flood_unknown = args.flood_unknown;
 
-    let mut rx = tx.subscribe();
+    // Re-probe statically-configured peers that haven't registered themselves yet, so a peer
+    // that was down at startup (or has since restarted) rejoins without operator intervention.
+    {
+        let peer_list = peer_list.clone();
+        let data_port = args.port;
+
+        // Probes go out from a control socket so the peer's HelloAck comes back to
+        // `control_handler`, which registers the peer under its data port.
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PEER_PROBE_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                let pending: Vec<SocketAddr> = {
+                    let peer_list = peer_list.lock().unwrap();
+                    peer_list
+                        .reconnect_peers()
+                        .iter()
+                        .filter(|addr| !peer_list.is_live(&SocketAddr::new(addr.ip(), data_port)))
+                        .cloned()
+                        .collect()
+                };
+
+                let hello = ControlMessage::Hello.encode();
+                for addr in pending {
+                    let Some(sock) = control_socks.iter().find(|sock| {
+                        sock.local_addr().map_or(false, |local| local.is_ipv4() == addr.is_ipv4())
+                    }) else {
+                        warn!("No control socket of {:?}'s address family to probe from", addr);
+                        continue;
+                    };
+
+                    trace!("Probing reconnect peer {:?}", addr);
+                    if let Err(e) = sock.send_to(&hello, addr).await {
+                        warn!("Failed to probe peer {:?}: {:?}", addr, e);
+                    }
+                }
+            }
+        });
+    }
+
+    let forward_ctx = Arc::new(ForwardContext {
+        forwarding_table,
+        peer_list,
+        transmit_sock_v4,
+        transmit_sock_v6,
+        flood_unknown,
+        protocol,
+    });
+
+    // Drive the catch-all queue on the main task; it spins up a dedicated per-source queue (and
+    // consumer) the first time each source address is seen.
+    run_catch_all(catch_all_rx, demux, forward_ctx).await;
+
+    Ok(())
+}
+
+const RENDEZVOUS_REGISTER_TIMEOUT: Duration = Duration::from_secs(3);
+const RENDEZVOUS_REGISTER_MAX_ATTEMPTS: u32 = 5;
+
+/// Sends `RendezvousHello` to `server` and waits for the `RendezvousPeers` reply, retrying with
+/// a fixed timeout if one never arrives - a single dropped UDP packet in either direction would
+/// otherwise hang the client forever.
+async fn register_with_rendezvous(
+    sock: &UdpSocket,
+    server: SocketAddr,
+) -> io::Result<Vec<SocketAddr>> {
+    let hello = ControlMessage::RendezvousHello.encode();
+    let mut buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+    for attempt in 1..=RENDEZVOUS_REGISTER_MAX_ATTEMPTS {
+        sock.send_to(&hello, server).await?;
+
+        let reply = tokio::time::timeout(RENDEZVOUS_REGISTER_TIMEOUT, async {
+            loop {
+                let (len, source_addr) = sock.recv_from(&mut buf).await?;
+                if source_addr != server {
+                    trace!("Ignoring datagram from unexpected source {:?}", source_addr);
+                    continue;
+                }
+
+                if let Some(ControlMessage::RendezvousPeers(peers)) =
+                    ControlMessage::decode(&buf[..len])
+                {
+                    return Ok::<_, io::Error>(peers);
+                }
+            }
+        })
+        .await;
+
+        match reply {
+            Ok(result) => return result,
+            Err(_) => warn!(
+                "No reply from rendezvous server {:?} after {:?} (attempt {}/{})",
+                server, RENDEZVOUS_REGISTER_TIMEOUT, attempt, RENDEZVOUS_REGISTER_MAX_ATTEMPTS
+            ),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "rendezvous server {server:?} did not reply after {RENDEZVOUS_REGISTER_MAX_ATTEMPTS} attempts"
+        ),
+    ))
+}
+
+/// Register with a rendezvous server, print the peer endpoints it hands back, and keep punching
+/// them to hold the NAT mapping open. Since the server only answers a `RendezvousHello` with the
+/// peers registered so far, we keep re-sending it alongside the keepalive pings so peers that
+/// join after us are still discovered.
+async fn run_rendezvous_client(args: RendezvousClientArgs) -> io::Result<()> {
+    info!("Registering with rendezvous server {:?}", args.rendezvous_server);
+
+    let sock = bind_reuseport(args.bind).expect("Could not bind rendezvous client socket");
+
+    let peers = register_with_rendezvous(&sock, args.rendezvous_server).await?;
+    info!("Learned {} peer endpoint(s): {:?}", peers.len(), peers);
+    println!("Learned peer endpoints: {:?}", peers);
+
+    let mut peers: HashSet<SocketAddr> = peers.into_iter().collect();
+    let keepalive_interval = Duration::from_secs(args.keepalive_interval);
+    let mut interval = tokio::time::interval(keepalive_interval);
+    let mut buf: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
 
     loop {
-        match rx.recv().await {
-            Ok((buf, _source_addr)) => {
-                let transmit_sock = transmit_sock.clone();
-                let transmit_addresses = transmit_addresses.clone();
-
-                tokio::spawn(async move {
-                    for transmit_address in transmit_addresses.iter() {
-                        match transmit_sock.send_to(&buf, transmit_address).await {
-                            Ok(n) => debug!("Sent {n} bytes to {transmit_address}"),
-                            Err(e) => error!("Send failed to {:?}, {:?}", transmit_address, e),
-                        }
+        tokio::select! {
+            _ = interval.tick() => {
+                let rendezvous_hello = ControlMessage::RendezvousHello.encode();
+                if let Err(e) = sock.send_to(&rendezvous_hello, args.rendezvous_server).await {
+                    warn!("Failed to re-register with rendezvous server: {:?}", e);
+                }
+
+                let hello = ControlMessage::Hello.encode();
+                for peer in &peers {
+                    trace!("Punching keepalive to {:?}", peer);
+                    if let Err(e) = sock.send_to(&hello, peer).await {
+                        warn!("Keepalive to {:?} failed: {:?}", peer, e);
                     }
-                });
+                }
             }
-            Err(e) => {
-                // TODO: This isn't quite the correct error message.
-                // TODO: check for `Lagged` message
-                error!("Receive failed: {:?}", e);
+            result = sock.recv_from(&mut buf) => {
+                let (len, source_addr) = result?;
+                if source_addr != args.rendezvous_server {
+                    trace!("Ignoring datagram from unexpected source {:?}", source_addr);
+                    continue;
+                }
+
+                if let Some(ControlMessage::RendezvousPeers(new_peers)) = ControlMessage::decode(&buf[..len]) {
+                    for peer in new_peers {
+                        if peers.insert(peer) {
+                            info!("Learned new peer endpoint {:?}", peer);
+                        }
+                    }
+                }
             }
         }
     }
 }
 
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    match Cli::parse() {
+        Cli::Relay(args) => {
+            env_logger::Builder::new()
+                .filter_level(args.verbose.log_level_filter())
+                .init();
+            run_relay(args).await
+        }
+        Cli::RendezvousClient(args) => {
+            env_logger::Builder::new()
+                .filter_level(args.verbose.log_level_filter())
+                .init();
+            run_rendezvous_client(args).await
+        }
+    }
+}
+
 #[tokio::test]
 async fn blah() {
     assert!(true);
@@ -0,0 +1,304 @@
+use log::{debug, info, trace};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"URLY";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ControlMessage {
+    Hello,
+    HelloAck,
+    RendezvousHello,
+    RendezvousPeers(Vec<SocketAddr>),
+}
+
+impl ControlMessage {
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 5 || &buf[0..4] != MAGIC {
+            return None;
+        }
+
+        match buf[4] {
+            0 if buf.len() == 5 => Some(ControlMessage::Hello),
+            1 if buf.len() == 5 => Some(ControlMessage::HelloAck),
+            2 if buf.len() == 5 => Some(ControlMessage::RendezvousHello),
+            3 => decode_peers(&buf[5..]).map(ControlMessage::RendezvousPeers),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        match self {
+            ControlMessage::Hello => out.push(0),
+            ControlMessage::HelloAck => out.push(1),
+            ControlMessage::RendezvousHello => out.push(2),
+            ControlMessage::RendezvousPeers(peers) => {
+                out.push(3);
+                encode_peers(peers, &mut out);
+            }
+        }
+        out
+    }
+}
+
+fn encode_peers(peers: &[SocketAddr], out: &mut Vec<u8>) {
+    for addr in peers {
+        match addr {
+            SocketAddr::V4(addr) => {
+                out.push(4);
+                out.extend_from_slice(&addr.ip().octets());
+                out.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            SocketAddr::V6(addr) => {
+                out.push(6);
+                out.extend_from_slice(&addr.ip().octets());
+                out.extend_from_slice(&addr.port().to_be_bytes());
+            }
+        }
+    }
+}
+
+fn decode_peers(mut buf: &[u8]) -> Option<Vec<SocketAddr>> {
+    let mut peers = Vec::new();
+
+    while !buf.is_empty() {
+        let family = buf[0];
+        buf = &buf[1..];
+
+        let addr_len = match family {
+            4 => 4,
+            6 => 16,
+            _ => return None,
+        };
+
+        if buf.len() < addr_len + 2 {
+            return None;
+        }
+
+        let (ip_bytes, rest) = buf.split_at(addr_len);
+        let (port_bytes, rest) = rest.split_at(2);
+        let port = u16::from_be_bytes(port_bytes.try_into().ok()?);
+
+        let addr = match family {
+            4 => SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(<[u8; 4]>::try_from(ip_bytes).ok()?),
+                port,
+            )),
+            6 => SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(<[u8; 16]>::try_from(ip_bytes).ok()?),
+                port,
+                0,
+                0,
+            )),
+            _ => unreachable!(),
+        };
+
+        peers.push(addr);
+        buf = rest;
+    }
+
+    Some(peers)
+}
+
+pub struct PeerList {
+    // `None` exempts a statically-seeded entry from pruning.
+    live: HashMap<SocketAddr, Option<Instant>>,
+    reconnect_peers: Vec<SocketAddr>,
+}
+
+impl PeerList {
+    pub fn new(reconnect_peers: Vec<SocketAddr>) -> Self {
+        Self {
+            live: HashMap::new(),
+            reconnect_peers,
+        }
+    }
+
+    pub fn register_static(&mut self, addr: SocketAddr) {
+        self.live.insert(addr, None);
+    }
+
+    pub fn register(&mut self, addr: SocketAddr) {
+        match self.live.insert(addr, Some(Instant::now())) {
+            None => info!("Registered new peer {:?}", addr),
+            Some(None) => {
+                // Was statically-seeded; keep it permanent rather than letting it decay.
+                self.live.insert(addr, None);
+                trace!("Refreshed heartbeat for static peer {:?}", addr);
+            }
+            Some(Some(_)) => trace!("Refreshed heartbeat for peer {:?}", addr),
+        }
+    }
+
+    pub fn prune(&mut self, timeout: Duration) {
+        let before = self.live.len();
+        let now = Instant::now();
+        self.live.retain(|_, last_seen| match last_seen {
+            None => true,
+            Some(last_seen) => now.duration_since(*last_seen) < timeout,
+        });
+
+        let removed = before - self.live.len();
+        if removed > 0 {
+            debug!("Pruned {} stale peers", removed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<SocketAddr> {
+        self.live.keys().cloned().collect()
+    }
+
+    pub fn is_live(&self, addr: &SocketAddr) -> bool {
+        self.live.contains_key(addr)
+    }
+
+    pub fn reconnect_peers(&self) -> &[SocketAddr] {
+        &self.reconnect_peers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_rejects_too_short() {
+        assert_eq!(ControlMessage::decode(b"URL"), None);
+        assert_eq!(ControlMessage::decode(b""), None);
+    }
+
+    #[test]
+    fn decode_rejects_wrong_magic() {
+        assert_eq!(ControlMessage::decode(b"XXXX\x00"), None);
+    }
+
+    #[test]
+    fn decode_rejects_unknown_tag() {
+        assert_eq!(ControlMessage::decode(b"URLY\xff"), None);
+    }
+
+    #[test]
+    fn decode_rejects_trailing_bytes_on_fixed_messages() {
+        assert_eq!(ControlMessage::decode(b"URLY\x00\x00"), None);
+        assert_eq!(ControlMessage::decode(b"URLY\x01extra"), None);
+    }
+
+    #[test]
+    fn hello_round_trips() {
+        let encoded = ControlMessage::Hello.encode();
+        assert_eq!(ControlMessage::decode(&encoded), Some(ControlMessage::Hello));
+    }
+
+    #[test]
+    fn hello_ack_round_trips() {
+        let encoded = ControlMessage::HelloAck.encode();
+        assert_eq!(ControlMessage::decode(&encoded), Some(ControlMessage::HelloAck));
+    }
+
+    #[test]
+    fn rendezvous_hello_round_trips() {
+        let encoded = ControlMessage::RendezvousHello.encode();
+        assert_eq!(
+            ControlMessage::decode(&encoded),
+            Some(ControlMessage::RendezvousHello)
+        );
+    }
+
+    #[test]
+    fn rendezvous_peers_round_trips_empty() {
+        let msg = ControlMessage::RendezvousPeers(Vec::new());
+        assert_eq!(ControlMessage::decode(&msg.encode()), Some(msg));
+    }
+
+    #[test]
+    fn rendezvous_peers_round_trips_mixed_families() {
+        let peers = vec![
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 1234)),
+            SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 5678, 0, 0)),
+        ];
+        let msg = ControlMessage::RendezvousPeers(peers);
+        assert_eq!(ControlMessage::decode(&msg.encode()), Some(msg));
+    }
+
+    #[test]
+    fn rendezvous_peers_rejects_truncated_entry() {
+        let mut buf = b"URLY\x03".to_vec();
+        buf.push(4); // claims an IPv4 entry follows
+        buf.extend_from_slice(&[127, 0, 0, 1]); // missing the trailing port bytes
+        assert_eq!(ControlMessage::decode(&buf), None);
+    }
+
+    #[test]
+    fn rendezvous_peers_rejects_unknown_family() {
+        let mut buf = b"URLY\x03".to_vec();
+        buf.push(7);
+        assert_eq!(ControlMessage::decode(&buf), None);
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port))
+    }
+
+    #[test]
+    fn register_marks_new_peer_live() {
+        let mut peer_list = PeerList::new(Vec::new());
+        assert!(!peer_list.is_live(&addr(1)));
+        peer_list.register(addr(1));
+        assert!(peer_list.is_live(&addr(1)));
+    }
+
+    #[test]
+    fn prune_evicts_dynamic_peers_after_timeout() {
+        let mut peer_list = PeerList::new(Vec::new());
+        peer_list.register(addr(1));
+        std::thread::sleep(Duration::from_millis(20));
+
+        peer_list.prune(Duration::from_millis(10));
+
+        assert!(!peer_list.is_live(&addr(1)));
+    }
+
+    #[test]
+    fn prune_never_evicts_static_peers() {
+        let mut peer_list = PeerList::new(Vec::new());
+        peer_list.register_static(addr(1));
+        std::thread::sleep(Duration::from_millis(20));
+
+        peer_list.prune(Duration::from_millis(10));
+
+        assert!(peer_list.is_live(&addr(1)));
+    }
+
+    #[test]
+    fn registering_a_static_peer_keeps_it_permanent() {
+        let mut peer_list = PeerList::new(Vec::new());
+        peer_list.register_static(addr(1));
+        peer_list.register(addr(1));
+        std::thread::sleep(Duration::from_millis(20));
+
+        peer_list.prune(Duration::from_millis(10));
+
+        assert!(peer_list.is_live(&addr(1)));
+    }
+
+    #[test]
+    fn snapshot_reflects_live_peers() {
+        let mut peer_list = PeerList::new(Vec::new());
+        peer_list.register_static(addr(1));
+        peer_list.register(addr(2));
+
+        let mut snapshot = peer_list.snapshot();
+        snapshot.sort();
+        let mut expected = vec![addr(1), addr(2)];
+        expected.sort();
+        assert_eq!(snapshot, expected);
+    }
+
+    #[test]
+    fn reconnect_peers_returns_what_was_configured() {
+        let peer_list = PeerList::new(vec![addr(1), addr(2)]);
+        assert_eq!(peer_list.reconnect_peers(), &[addr(1), addr(2)]);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// Used as the forwarding table's key, so different protocols can key on whatever kind of
+// address their header actually carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InnerAddr {
+    Ip(IpAddr),
+    Mac([u8; 6]),
+}
+
+impl fmt::Display for InnerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InnerAddr::Ip(addr) => write!(f, "{addr}"),
+            InnerAddr::Mac(bytes) => write!(
+                f,
+                "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError;
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not parse inner addresses from payload")
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub trait Protocol: Send + Sync {
+    fn parse(&self, buf: &[u8]) -> Result<(InnerAddr, InnerAddr), ParseError>;
+}
+
+// Never resolves inner addresses, so every packet falls back to flooding - today's behavior.
+pub struct RawProtocol;
+
+impl Protocol for RawProtocol {
+    fn parse(&self, _buf: &[u8]) -> Result<(InnerAddr, InnerAddr), ParseError> {
+        Err(ParseError)
+    }
+}
+
+pub struct EthernetProtocol;
+
+impl Protocol for EthernetProtocol {
+    fn parse(&self, buf: &[u8]) -> Result<(InnerAddr, InnerAddr), ParseError> {
+        if buf.len() < 14 {
+            return Err(ParseError);
+        }
+
+        let dst: [u8; 6] = buf[0..6].try_into().map_err(|_| ParseError)?;
+        let src: [u8; 6] = buf[6..12].try_into().map_err(|_| ParseError)?;
+
+        Ok((InnerAddr::Mac(src), InnerAddr::Mac(dst)))
+    }
+}
+
+pub struct IpProtocol;
+
+impl Protocol for IpProtocol {
+    fn parse(&self, buf: &[u8]) -> Result<(InnerAddr, InnerAddr), ParseError> {
+        let version = buf.first().ok_or(ParseError)? >> 4;
+
+        match version {
+            4 if buf.len() >= 20 => {
+                let src: [u8; 4] = buf[12..16].try_into().map_err(|_| ParseError)?;
+                let dst: [u8; 4] = buf[16..20].try_into().map_err(|_| ParseError)?;
+                Ok((
+                    InnerAddr::Ip(IpAddr::from(Ipv4Addr::from(src))),
+                    InnerAddr::Ip(IpAddr::from(Ipv4Addr::from(dst))),
+                ))
+            }
+            6 if buf.len() >= 40 => {
+                let src: [u8; 16] = buf[8..24].try_into().map_err(|_| ParseError)?;
+                let dst: [u8; 16] = buf[24..40].try_into().map_err(|_| ParseError)?;
+                Ok((
+                    InnerAddr::Ip(IpAddr::from(Ipv6Addr::from(src))),
+                    InnerAddr::Ip(IpAddr::from(Ipv6Addr::from(dst))),
+                ))
+            }
+            _ => Err(ParseError),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolKind {
+    Raw,
+    Ethernet,
+    Ip,
+}
+
+impl ProtocolKind {
+    pub fn build(self) -> Box<dyn Protocol> {
+        match self {
+            ProtocolKind::Raw => Box::new(RawProtocol),
+            ProtocolKind::Ethernet => Box::new(EthernetProtocol),
+            ProtocolKind::Ip => Box::new(IpProtocol),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_never_parses() {
+        let buf = [0x45u8; 40];
+        assert!(RawProtocol.parse(&buf).is_err());
+        assert!(RawProtocol.parse(&[]).is_err());
+    }
+
+    #[test]
+    fn ethernet_rejects_short_buffer() {
+        let buf = [0u8; 13];
+        assert!(EthernetProtocol.parse(&buf).is_err());
+    }
+
+    #[test]
+    fn ethernet_parses_header() {
+        let mut buf = [0u8; 14];
+        buf[0..6].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        buf[6..12].copy_from_slice(&[6, 5, 4, 3, 2, 1]);
+
+        let (src, dst) = EthernetProtocol.parse(&buf).unwrap();
+        assert_eq!(src, InnerAddr::Mac([6, 5, 4, 3, 2, 1]));
+        assert_eq!(dst, InnerAddr::Mac([1, 2, 3, 4, 5, 6]));
+    }
+
+    #[test]
+    fn ip_rejects_empty_buffer() {
+        assert!(IpProtocol.parse(&[]).is_err());
+    }
+
+    #[test]
+    fn ip_rejects_bad_version_nibble() {
+        let buf = [0x55u8; 20];
+        assert!(IpProtocol.parse(&buf).is_err());
+    }
+
+    #[test]
+    fn ip_rejects_truncated_v4_header() {
+        let mut buf = [0u8; 19];
+        buf[0] = 0x45;
+        assert!(IpProtocol.parse(&buf).is_err());
+    }
+
+    #[test]
+    fn ip_parses_v4_header() {
+        let mut buf = [0u8; 20];
+        buf[0] = 0x45;
+        buf[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        buf[16..20].copy_from_slice(&[10, 0, 0, 2]);
+
+        let (src, dst) = IpProtocol.parse(&buf).unwrap();
+        assert_eq!(src, InnerAddr::Ip(IpAddr::from(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(dst, InnerAddr::Ip(IpAddr::from(Ipv4Addr::new(10, 0, 0, 2))));
+    }
+
+    #[test]
+    fn ip_rejects_truncated_v6_header() {
+        let mut buf = [0u8; 39];
+        buf[0] = 0x60;
+        assert!(IpProtocol.parse(&buf).is_err());
+    }
+
+    #[test]
+    fn ip_parses_v6_header() {
+        let mut buf = [0u8; 40];
+        buf[0] = 0x60;
+        buf[8..24].copy_from_slice(&[1; 16]);
+        buf[24..40].copy_from_slice(&[2; 16]);
+
+        let (src, dst) = IpProtocol.parse(&buf).unwrap();
+        assert_eq!(src, InnerAddr::Ip(IpAddr::from(Ipv6Addr::from([1; 16]))));
+        assert_eq!(dst, InnerAddr::Ip(IpAddr::from(Ipv6Addr::from([2; 16]))));
+    }
+}
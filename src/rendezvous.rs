@@ -0,0 +1,95 @@
+use log::{debug, info};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// Peers are keyed by the address we actually observed them from, never what they claim.
+pub struct RendezvousTable {
+    peers: HashMap<SocketAddr, Instant>,
+}
+
+impl RendezvousTable {
+    pub fn new() -> Self {
+        Self {
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, observed_addr: SocketAddr) {
+        let is_new = self.peers.insert(observed_addr, Instant::now()).is_none();
+        if is_new {
+            info!("Rendezvous: registered peer {:?}", observed_addr);
+        }
+    }
+
+    pub fn peers_excluding(&self, observed_addr: &SocketAddr) -> Vec<SocketAddr> {
+        self.peers
+            .keys()
+            .filter(|addr| *addr != observed_addr)
+            .cloned()
+            .collect()
+    }
+
+    pub fn prune(&mut self, timeout: Duration) {
+        let before = self.peers.len();
+        let now = Instant::now();
+        self.peers
+            .retain(|_, last_seen| now.duration_since(*last_seen) < timeout);
+
+        let removed = before - self.peers.len();
+        if removed > 0 {
+            debug!("Rendezvous: pruned {} stale peers", removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::thread::sleep;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)
+    }
+
+    #[test]
+    fn peers_excluding_omits_only_the_given_address() {
+        let mut table = RendezvousTable::new();
+        table.register(addr(1));
+        table.register(addr(2));
+
+        let mut peers = table.peers_excluding(&addr(1));
+        peers.sort();
+        assert_eq!(peers, vec![addr(2)]);
+    }
+
+    #[test]
+    fn peers_excluding_is_empty_for_a_lone_peer() {
+        let mut table = RendezvousTable::new();
+        table.register(addr(1));
+        assert_eq!(table.peers_excluding(&addr(1)), Vec::new());
+    }
+
+    #[test]
+    fn register_again_refreshes_rather_than_duplicates() {
+        let mut table = RendezvousTable::new();
+        table.register(addr(1));
+        table.register(addr(1));
+        assert_eq!(table.peers_excluding(&addr(2)), vec![addr(1)]);
+    }
+
+    #[test]
+    fn prune_evicts_stale_peers_only() {
+        let mut table = RendezvousTable::new();
+        table.register(addr(1));
+        sleep(Duration::from_millis(20));
+        table.register(addr(2));
+
+        table.prune(Duration::from_millis(10));
+
+        let mut peers = table.peers_excluding(&addr(999));
+        peers.sort();
+        assert_eq!(peers, vec![addr(2)]);
+    }
+}